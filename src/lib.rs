@@ -10,45 +10,185 @@
 //! [`Decoder`](struct.Decoder.html) or [`Encoder`](struct.Encoder.html). If you want both, you
 //! better use [`Codec`](struct.Codec.html).
 //!
-//! Note that this is useful if the CBOR itself defines the frames. If the messages are delimited
-//! in some other way (eg. length-prefix encoding) and CBOR is only the payload, you'd use a codec
-//! for the other framing and use `.map` on the received stream and sink to convert the messages.
+//! By default, the CBOR itself defines the frames (each frame is exactly as long as the encoded
+//! item needs). If your peer instead expects messages delimited some other way (eg. a
+//! length-prefix header), set [`FrameMode::LengthDelimited`](enum.FrameMode.html) on the
+//! `Decoder`/`Encoder`/`Codec` instead of stacking a second codec on top.
+//!
+//! If a single connection multiplexes several message shapes and the right `Item` type can only
+//! be known after looking at the frame itself (eg. a discriminator field in a tagged union), use
+//! [`Decoder::as_value_decoder`](struct.Decoder.html#method.as_value_decoder) or
+//! [`Codec::decode_value`](struct.Codec.html#method.decode_value) to decode into
+//! `serde_cbor::Value` first, then `serde_cbor::value::from_value` into the concrete type once
+//! it's known.
 
 extern crate bytes;
+#[cfg(feature = "deflate")]
+extern crate flate2;
 extern crate serde;
 extern crate serde_cbor;
 extern crate tokio_io;
+#[cfg(feature = "zstd")]
+extern crate zstd;
 
 use std::default::Default;
-use std::io::{ErrorKind, Read, Result as IoResult, Write};
+#[cfg(feature = "deflate")]
+use std::io::Read;
+use std::io::{Result as IoResult, Write};
 use std::marker::PhantomData;
 
 use bytes::BytesMut;
-use serde::{Deserialize, Serialize};
+#[cfg(feature = "deflate")]
+use flate2::read::DeflateDecoder;
+#[cfg(feature = "deflate")]
+use flate2::write::DeflateEncoder;
+#[cfg(feature = "deflate")]
+use flate2::Compression as Flate2Compression;
+use serde::de::DeserializeOwned;
+use serde::de::Error as _;
+use serde::Serialize;
 use serde_cbor::de::Deserializer;
 use serde_cbor::error::Error as CborError;
-use serde_cbor::ser::Serializer;
+use serde_cbor::ser::{IoWrite, Serializer};
 use tokio_io::codec::{Decoder as IoDecoder, Encoder as IoEncoder};
 
-/// A `Read` wrapper that also counts the used bytes.
+/// Builds the error returned when a frame doesn't fit within a configured `max_size`.
+fn frame_too_large(max_size: usize) -> CborError {
+    CborError::custom(format!(
+        "frame exceeds the configured maximum size of {} bytes",
+        max_size
+    ))
+}
+
+/// Adds a header's length to its declared body length to get the total frame length, checking
+/// for overflow and (if configured) against `max_size` before the result is ever used to slice a
+/// buffer.
 ///
-/// This wraps a `Read` into another `Read` that keeps track of how many bytes were read. This is
-/// needed, as there's no way to get the position out of the CBOR decoder.
-struct Counted<'a, R: 'a> {
-    r: &'a mut R,
-    pos: &'a mut usize,
+/// A hostile peer can declare a body length up to `u64::MAX`; without this check, adding it to
+/// the header length can overflow `usize` and wrap around to something smaller than the header
+/// itself, which then panics on the slice that's supposed to isolate the body.
+fn checked_frame_len(header_len: usize, body_len: usize, max_size: Option<usize>) -> Result<usize, CborError> {
+    match (header_len.checked_add(body_len), max_size) {
+        (Some(frame_len), Some(max_size)) if frame_len > max_size => Err(frame_too_large(max_size)),
+        (Some(frame_len), _) => Ok(frame_len),
+        (None, Some(max_size)) => Err(frame_too_large(max_size)),
+        (None, None) => Err(CborError::custom(
+            "frame's declared length overflows when added to its header size",
+        )),
+    }
 }
 
-impl<'a, R: Read> Read for Counted<'a, R> {
-    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
-        match self.r.read(buf) {
-            Ok(size) => {
-                *self.pos += size;
-                Ok(size)
-            },
-            e => e,
-        }
+/// The 3 bytes CBOR uses to encode its self-describe tag (tag 55799).
+const SD_TAG: [u8; 3] = [0xd9, 0xd9, 0xf7];
+
+/// Describes the behaviour of self-describe tags.
+///
+/// CBOR defines a tag which can be used to recognize a document as being CBOR (it's sometimes
+/// called „magic“). On the encoding side this specifies if it should be placed in front of the
+/// data; on the decoding side (see [`Decoder::expect_sd`](struct.Decoder.html#method.expect_sd))
+/// it specifies whether it is required to be there.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SdMode {
+    /// Places (when encoding) or expects (when decoding) the tag in front of each frame.
+    Always,
+    /// Places (when encoding) or expects (when decoding) the tag in front of the first frame only.
+    Once,
+    /// Doesn't place the tag when encoding, and doesn't require it when decoding.
+    Never,
+}
+
+/// Controls how frame boundaries are found on the wire.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub enum FrameMode {
+    /// Relies on CBOR's own self-delimiting encoding: a frame is exactly as long as the next
+    /// complete CBOR item needs to be. This is the default.
+    #[default]
+    SelfDelimiting,
+    /// Prefixes each frame with a big-endian length header of `header_bytes` bytes (1 to 8),
+    /// matching common length-delimited wire formats (eg. `tokio_io`'s `LengthDelimitedCodec`).
+    LengthDelimited {
+        /// How many bytes the length prefix itself takes up.
+        header_bytes: usize,
+    },
+}
+
+/// Per-frame compression algorithms.
+///
+/// When set on the [`Encoder`](struct.Encoder.html) or [`Decoder`](struct.Decoder.html), each
+/// frame is compressed independently before it hits the wire, and decompressed before the CBOR
+/// inside it is looked at. Because compressed data doesn't self-delimit the way raw CBOR does,
+/// compressed frames are wrapped in a CBOR byte-string header carrying their length, so framing
+/// still works. Picking a variant requires enabling its cargo feature, which keeps the default
+/// build dependency-light.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Compression {
+    /// DEFLATE, via the `flate2` crate. Requires the `deflate` feature.
+    #[cfg(feature = "deflate")]
+    Deflate,
+    /// Zstandard, via the `zstd` crate. Requires the `zstd` feature.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+/// Compresses `raw` with the given algorithm.
+#[allow(unused_variables)]
+fn compress(compression: Compression, raw: &[u8]) -> IoResult<Vec<u8>> {
+    match compression {
+        #[cfg(feature = "deflate")]
+        Compression::Deflate => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Flate2Compression::default());
+            encoder.write_all(raw)?;
+            encoder.finish()
+        },
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => zstd::encode_all(raw, 0),
+    }
+}
+
+/// Decompresses `compressed`, the inverse of `compress`.
+#[allow(unused_variables)]
+fn decompress(compression: Compression, compressed: &[u8]) -> IoResult<Vec<u8>> {
+    match compression {
+        #[cfg(feature = "deflate")]
+        Compression::Deflate => {
+            let mut decoder = DeflateDecoder::new(compressed);
+            let mut raw = Vec::new();
+            decoder.read_to_end(&mut raw)?;
+            Ok(raw)
+        },
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => zstd::decode_all(compressed),
+    }
+}
+
+/// The fixed size of the length header we put in front of a compressed frame: one CBOR byte-string
+/// major-type byte followed by an 8-byte big-endian length.
+const COMPRESSED_HEADER_LEN: usize = 9;
+
+/// Writes the length header for a compressed frame.
+///
+/// This is a CBOR byte-string header (major type 2), always using the 8-byte length form, so it's
+/// trivial to write and to recognize again on the decoding side.
+fn write_compressed_header(dst: &mut BytesMut, len: usize) {
+    dst.extend(&[0x5b]);
+    let len = len as u64;
+    for i in (0..8).rev() {
+        dst.extend(&[(len >> (i * 8)) as u8]);
+    }
+}
+
+/// Reads the length header written by `write_compressed_header`, if it's fully buffered.
+///
+/// Returns the declared payload length on success.
+fn read_compressed_header(src: &[u8]) -> Option<usize> {
+    if src.len() < COMPRESSED_HEADER_LEN || src[0] != 0x5b {
+        return None;
+    }
+    let mut len = 0u64;
+    for &byte in &src[1..COMPRESSED_HEADER_LEN] {
+        len = (len << 8) | u64::from(byte);
     }
+    Some(len as usize)
 }
 
 /// CBOR based decoder.
@@ -58,66 +198,235 @@ impl<'a, R: Read> Read for Counted<'a, R> {
 #[derive(Clone, Debug)]
 pub struct Decoder<Item> {
     _data: PhantomData<*const Item>,
+    max_size: Option<usize>,
+    expect_sd: SdMode,
+    compression: Option<Compression>,
+    frame_mode: FrameMode,
 }
 
-impl<'de, Item: Deserialize<'de>> Decoder<Item> {
+impl<Item: DeserializeOwned> Decoder<Item> {
     /// Creates a new decoder.
     pub fn new() -> Self {
-        Self { _data: PhantomData }
+        Self {
+            _data: PhantomData,
+            max_size: None,
+            expect_sd: SdMode::Never,
+            compression: None,
+            frame_mode: FrameMode::SelfDelimiting,
+        }
+    }
+    /// Turns the decoder into one with a configured maximum frame size.
+    ///
+    /// If a frame would need more than `max_size` bytes to parse, or the buffered, still
+    /// incomplete data for the current frame already exceeds it, decoding fails with an error
+    /// instead of letting the peer force us to keep growing the input buffer. By default there's
+    /// no limit.
+    pub fn max_size(self, max_size: usize) -> Self {
+        Self {
+            max_size: Some(max_size),
+            ..self
+        }
+    }
+    /// Turns the decoder into one with a configured expectation about the self-describe tag.
+    ///
+    /// With `SdMode::Always` or `SdMode::Once`, a frame missing the leading self-describe tag is
+    /// rejected instead of being decoded anyway. This lets a receiver insist on a „every frame is
+    /// tagged“ contract with the peer, which is handy for protocol sniffing or versioning. The tag
+    /// itself, when present, is stripped before the rest of the frame is handed to `Item`'s
+    /// `Deserialize` implementation. By default (`SdMode::Never`), the tag is neither required nor
+    /// stripped here; `serde_cbor` already skips it transparently if it's there.
+    pub fn expect_sd(self, expect_sd: SdMode) -> Self {
+        Self { expect_sd: expect_sd, ..self }
+    }
+    /// Turns the decoder into one that expects each frame to be compressed.
+    ///
+    /// The counterpart encoder must be configured with the same `Compression` for this to work.
+    pub fn compressed(self, compression: Compression) -> Self {
+        Self {
+            compression: Some(compression),
+            ..self
+        }
+    }
+    /// Turns the decoder into one using the given `FrameMode` to find frame boundaries.
+    pub fn frame_mode(self, frame_mode: FrameMode) -> Self {
+        Self { frame_mode: frame_mode, ..self }
+    }
+    /// Returns an equivalent decoder for `serde_cbor::Value`, sharing this decoder's `max_size`,
+    /// `expect_sd`, `compression` and `frame_mode`.
+    ///
+    /// This is handy for protocols multiplexing several message shapes over one connection:
+    /// decode a frame into `Value` first, branch on whatever discriminates the shapes, then use
+    /// `serde_cbor::value::from_value` to get the concrete type the discriminator calls for.
+    pub fn as_value_decoder(&self) -> Decoder<serde_cbor::Value> {
+        Decoder {
+            _data: PhantomData,
+            max_size: self.max_size,
+            expect_sd: self.expect_sd.clone(),
+            compression: self.compression,
+            frame_mode: self.frame_mode,
+        }
     }
 }
 
-impl<'de, Item: Deserialize<'de>> Default for Decoder<Item> {
+impl<Item: DeserializeOwned> Default for Decoder<Item> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<'de, Item: Deserialize<'de>> IoDecoder for Decoder<Item> {
+impl<Item: DeserializeOwned> IoDecoder for Decoder<Item> {
     type Item = Item;
     type Error = CborError;
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Item>, CborError> {
-        // Try to read the value using the Cbor's deserializer, but keep track of how many data has
-        // been eaten.
-        let mut pos = 0;
-        let result = {
-            let mut slice: &[u8] = src;
-            let reader = Counted {
-                r: &mut slice,
-                pos: &mut pos,
-            };
-            // Use the deserializer directly, instead of using `deserialize_from`. We explicitly do
-            // *not* want to check that there are no trailing bytes ‒ there may be, and they are
-            // the next frame.
-            let mut deserializer = Deserializer::new(reader);
-            Item::deserialize(&mut deserializer)
-        };
-        match result {
-            // If we read the item, we also need to consume the corresponding bytes.
-            Ok(item) => {
-                src.split_to(pos);
-                Ok(Some(item))
+        match self.frame_mode {
+            FrameMode::SelfDelimiting => self.decode_self_delimiting(src),
+            FrameMode::LengthDelimited { header_bytes } => {
+                self.decode_length_delimited(src, header_bytes)
             },
-            // Sometimes the EOF is signalled as IO error
-            Err(CborError::Io(ref io)) if io.kind() == ErrorKind::UnexpectedEof => Ok(None),
-            // Any other error is simply passed through.
-            Err(e) => Err(e),
         }
     }
 }
 
-/// Describes the behaviour of self-describe tags.
-///
-/// CBOR defines a tag which can be used to recognize a document as being CBOR (it's sometimes
-/// called „magic“). This specifies if it should be present when encoding.
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub enum SdMode {
-    /// Places the tag in front of each encoded frame.
-    Always,
-    /// Places the tag in front of the first encoded frame.
-    Once,
-    /// Doesn't place the tag at all.
-    Never,
+impl<Item: DeserializeOwned> Decoder<Item> {
+    /// Decodes a frame whose boundary is CBOR's own self-delimiting encoding.
+    fn decode_self_delimiting(&mut self, src: &mut BytesMut) -> Result<Option<Item>, CborError> {
+        // Compressed frames carry their own length header on the wire instead of self-delimiting
+        // CBOR, and the self-describe tag (if any) lives *inside* the compressed payload, not in
+        // front of it, so the whole frame (tag included) is handled by `decode_compressed`.
+        if let Some(compression) = self.compression {
+            return self.decode_compressed(compression, src);
+        }
+        // If we're supposed to police the self-describe tag, deal with it (and strip it) before
+        // the real frame is even looked at, so the byte offset below stays exactly the size of
+        // the frame's own data.
+        let mut prefix = 0;
+        if self.expect_sd != SdMode::Never {
+            if src.len() < SD_TAG.len() {
+                // Not enough data buffered yet to even tell if the tag is there.
+                return Ok(None);
+            }
+            if src.starts_with(&SD_TAG) {
+                prefix = SD_TAG.len();
+                if self.expect_sd == SdMode::Once {
+                    self.expect_sd = SdMode::Never;
+                }
+            } else {
+                return Err(CborError::custom(
+                    "frame is missing the required CBOR self-describe tag",
+                ));
+            }
+        }
+        // Parse straight out of the buffered slice. The stream deserializer's `byte_offset` tells
+        // us exactly how much of it the item consumed, so there's no need to wrap the input in a
+        // counting `Read` any more, and no heuristics around which IO error means "incomplete".
+        let slice: &[u8] = &src[prefix..];
+        let mut iter = Deserializer::from_slice(slice).into_iter::<Item>();
+        let item = match iter.next() {
+            Some(Ok(item)) => item,
+            Some(Err(e)) => {
+                return if e.is_eof() {
+                    // Not enough data buffered yet; leave it alone until more arrives. But if
+                    // what's already buffered for this frame alone is over the limit, it's never
+                    // going to fit, so don't just wait around for more of it.
+                    match self.max_size {
+                        Some(max_size) if slice.len() > max_size => Err(frame_too_large(max_size)),
+                        _ => Ok(None),
+                    }
+                } else {
+                    // Any other error is simply passed through, and the buffer is left intact so
+                    // the caller can decide what to do (eg. drop the connection).
+                    Err(e)
+                };
+            },
+            // An empty slice parses to no items at all; treat that the same as "not enough data".
+            None => return Ok(None),
+        };
+        let len = iter.byte_offset();
+        if let Some(max_size) = self.max_size {
+            if len > max_size {
+                return Err(frame_too_large(max_size));
+            }
+        }
+        // We read the item, so we also need to consume the corresponding bytes (including the
+        // self-describe prefix, if we stripped one).
+        src.split_to(prefix + len);
+        Ok(Some(item))
+    }
+
+    /// Decodes a single length-prefixed, compressed frame starting at `src[0]`.
+    fn decode_compressed(
+        &mut self,
+        compression: Compression,
+        src: &mut BytesMut,
+    ) -> Result<Option<Item>, CborError> {
+        let payload_len = match read_compressed_header(src) {
+            Some(len) => len,
+            // Not even the header has fully arrived yet.
+            None => return Ok(None),
+        };
+        let frame_len = checked_frame_len(COMPRESSED_HEADER_LEN, payload_len, self.max_size)?;
+        if src.len() < frame_len {
+            // The compressed payload hasn't fully arrived yet.
+            return Ok(None);
+        }
+        let compressed = &src[COMPRESSED_HEADER_LEN..frame_len];
+        let raw = decompress(compression, compressed).map_err(CborError::custom)?;
+        // The self-describe tag, if there's supposed to be one, was encoded before compression,
+        // so it's found (and stripped) here, on the decompressed bytes, rather than on the wire.
+        let mut prefix = 0;
+        if self.expect_sd != SdMode::Never {
+            if raw.len() < SD_TAG.len() || !raw.starts_with(&SD_TAG) {
+                return Err(CborError::custom(
+                    "frame is missing the required CBOR self-describe tag",
+                ));
+            }
+            prefix = SD_TAG.len();
+            if self.expect_sd == SdMode::Once {
+                self.expect_sd = SdMode::Never;
+            }
+        }
+        let item = serde_cbor::from_slice(&raw[prefix..])?;
+        src.split_to(frame_len);
+        Ok(Some(item))
+    }
+
+    /// Decodes a frame whose boundary is a fixed-size, big-endian length prefix.
+    fn decode_length_delimited(
+        &mut self,
+        src: &mut BytesMut,
+        header_bytes: usize,
+    ) -> Result<Option<Item>, CborError> {
+        if src.len() < header_bytes {
+            return Ok(None);
+        }
+        let mut len = 0u64;
+        for &byte in &src[..header_bytes] {
+            len = (len << 8) | u64::from(byte);
+        }
+        let len = len as usize;
+        let frame_len = checked_frame_len(header_bytes, len, self.max_size)?;
+        if src.len() < frame_len {
+            return Ok(None);
+        }
+        // Decode the CBOR item out of exactly the declared body, re-using the self-delimiting
+        // path (self-describe tag, compression, ...) on that isolated slice.
+        let mut body = BytesMut::from(&src[header_bytes..frame_len]);
+        let item = match self.decode_self_delimiting(&mut body)? {
+            Some(item) if body.is_empty() => item,
+            Some(_) => {
+                return Err(CborError::custom(
+                    "length-delimited frame has trailing bytes after a complete CBOR item",
+                ));
+            },
+            None => {
+                return Err(CborError::custom(
+                    "length-delimited frame's declared length doesn't contain a complete CBOR item",
+                ));
+            },
+        };
+        src.split_to(frame_len);
+        Ok(Some(item))
+    }
 }
 
 /// CBOR based encoder.
@@ -130,6 +439,8 @@ pub struct Encoder<Item> {
     _data: PhantomData<*const Item>,
     sd: SdMode,
     packed: bool,
+    compression: Option<Compression>,
+    frame_mode: FrameMode,
 }
 
 impl<Item: Serialize> Encoder<Item> {
@@ -142,6 +453,8 @@ impl<Item: Serialize> Encoder<Item> {
             _data: PhantomData,
             sd: SdMode::Never,
             packed: false,
+            compression: None,
+            frame_mode: FrameMode::SelfDelimiting,
         }
     }
     /// Turns the encoder into one with confifured self-describe behaviour.
@@ -159,6 +472,34 @@ impl<Item: Serialize> Encoder<Item> {
             ..self
         }
     }
+    /// Turns the encoder into one that compresses each frame.
+    ///
+    /// The counterpart decoder must be configured with the same `Compression` to understand the
+    /// result.
+    pub fn compressed(self, compression: Compression) -> Self {
+        Self {
+            compression: Some(compression),
+            ..self
+        }
+    }
+    /// Turns the encoder into one using the given `FrameMode` to delimit frames.
+    pub fn frame_mode(self, frame_mode: FrameMode) -> Self {
+        Self { frame_mode: frame_mode, ..self }
+    }
+    /// Serializes `item` into `writer`, applying the configured `sd`/`packed` settings.
+    fn serialize_into<W: Write>(&mut self, item: Item, writer: W) -> Result<(), CborError> {
+        let mut serializer = Serializer::new(IoWrite::new(writer));
+        if self.packed {
+            serializer = serializer.packed_format();
+        }
+        if self.sd != SdMode::Never {
+            serializer.self_describe()?;
+        }
+        if self.sd == SdMode::Once {
+            self.sd = SdMode::Never;
+        }
+        item.serialize(&mut serializer)
+    }
 }
 
 impl<Item: Serialize> Default for Encoder<Item> {
@@ -187,19 +528,50 @@ impl<Item: Serialize> IoEncoder for Encoder<Item> {
     type Item = Item;
     type Error = CborError;
     fn encode(&mut self, item: Item, dst: &mut BytesMut) -> Result<(), CborError> {
-        let writer = BytesWriter(dst);
-        let mut serializer = if self.packed {
-            Serializer::packed(writer)
-        } else {
-            Serializer::new(writer)
-        };
-        if self.sd != SdMode::Never {
-            serializer.self_describe()?;
+        match self.frame_mode {
+            FrameMode::SelfDelimiting => self.encode_self_delimiting(item, dst),
+            FrameMode::LengthDelimited { header_bytes } => {
+                self.encode_length_delimited(item, dst, header_bytes)
+            },
         }
-        if self.sd == SdMode::Once {
-            self.sd = SdMode::Never;
+    }
+}
+
+impl<Item: Serialize> Encoder<Item> {
+    /// Encodes a frame relying on nothing but CBOR's own self-delimiting encoding for framing.
+    fn encode_self_delimiting(&mut self, item: Item, dst: &mut BytesMut) -> Result<(), CborError> {
+        match self.compression {
+            None => self.serialize_into(item, BytesWriter(dst)),
+            Some(compression) => {
+                let mut raw = Vec::new();
+                self.serialize_into(item, &mut raw)?;
+                let compressed = compress(compression, &raw).map_err(CborError::custom)?;
+                write_compressed_header(dst, compressed.len());
+                dst.extend(&compressed);
+                Ok(())
+            },
         }
-        item.serialize(&mut serializer)
+    }
+
+    /// Encodes a frame prefixed with a fixed-size, big-endian length header.
+    ///
+    /// The frame's body is whatever `encode_self_delimiting` would have written on its own
+    /// (self-describe tag and compression included), just wrapped in the length prefix instead of
+    /// relying on it to self-delimit.
+    fn encode_length_delimited(
+        &mut self,
+        item: Item,
+        dst: &mut BytesMut,
+        header_bytes: usize,
+    ) -> Result<(), CborError> {
+        let mut body = BytesMut::new();
+        self.encode_self_delimiting(item, &mut body)?;
+        let len = body.len() as u64;
+        for i in (0..header_bytes).rev() {
+            dst.extend(&[(len >> (i * 8)) as u8]);
+        }
+        dst.extend(&body);
+        Ok(())
     }
 }
 
@@ -210,14 +582,19 @@ impl<Item: Serialize> IoEncoder for Encoder<Item> {
 pub struct Codec<Dec, Enc> {
     dec: Decoder<Dec>,
     enc: Encoder<Enc>,
+    /// Lazily built by `decode_value` and then reused, so that state the underlying `Value`
+    /// decoder mutates across frames (eg. `SdMode::Once` dropping its tag expectation after the
+    /// first frame) actually persists instead of being discarded after every call.
+    value_dec: Option<Decoder<serde_cbor::Value>>,
 }
 
-impl<'de, Dec: Deserialize<'de>, Enc: Serialize> Codec<Dec, Enc> {
+impl<Dec: DeserializeOwned, Enc: Serialize> Codec<Dec, Enc> {
     /// Creates a new codec
     pub fn new() -> Self {
         Self {
             dec: Decoder::new(),
             enc: Encoder::new(),
+            value_dec: None,
         }
     }
     /// Turns the internal encoder into one with confifured self-describe behaviour.
@@ -225,6 +602,7 @@ impl<'de, Dec: Deserialize<'de>, Enc: Serialize> Codec<Dec, Enc> {
         Self {
             dec: self.dec,
             enc: Encoder { sd: sd, ..self.enc },
+            value_dec: self.value_dec,
         }
     }
     /// Turns the internal encoder into one with configured packed encoding.
@@ -239,17 +617,80 @@ impl<'de, Dec: Deserialize<'de>, Enc: Serialize> Codec<Dec, Enc> {
                 packed: packed,
                 ..self.enc
             },
+            value_dec: self.value_dec,
         }
     }
+    /// Turns the internal decoder into one with a configured maximum frame size.
+    ///
+    /// See [`Decoder::max_size`](struct.Decoder.html#method.max_size).
+    pub fn max_size(self, max_size: usize) -> Self {
+        Self {
+            dec: self.dec.max_size(max_size),
+            enc: self.enc,
+            value_dec: self.value_dec,
+        }
+    }
+    /// Turns the internal decoder into one with a configured expectation about the self-describe
+    /// tag.
+    ///
+    /// See [`Decoder::expect_sd`](struct.Decoder.html#method.expect_sd).
+    pub fn expect_sd(self, expect_sd: SdMode) -> Self {
+        Self {
+            dec: self.dec.expect_sd(expect_sd),
+            enc: self.enc,
+            value_dec: self.value_dec,
+        }
+    }
+    /// Turns the internal decoder and encoder into ones that compress each frame.
+    ///
+    /// See [`Decoder::compressed`](struct.Decoder.html#method.compressed) and
+    /// [`Encoder::compressed`](struct.Encoder.html#method.compressed).
+    pub fn compressed(self, compression: Compression) -> Self {
+        Self {
+            dec: self.dec.compressed(compression),
+            enc: self.enc.compressed(compression),
+            value_dec: self.value_dec,
+        }
+    }
+    /// Turns the internal decoder and encoder into ones using the given `FrameMode` to find and
+    /// mark frame boundaries.
+    ///
+    /// See [`Decoder::frame_mode`](struct.Decoder.html#method.frame_mode) and
+    /// [`Encoder::frame_mode`](struct.Encoder.html#method.frame_mode).
+    pub fn frame_mode(self, frame_mode: FrameMode) -> Self {
+        Self {
+            dec: self.dec.frame_mode(frame_mode),
+            enc: self.enc.frame_mode(frame_mode),
+            value_dec: self.value_dec,
+        }
+    }
+    /// Decodes a single frame into a `serde_cbor::Value` instead of `Dec`, without consuming
+    /// anything beyond that one frame.
+    ///
+    /// See [`Decoder::as_value_decoder`](struct.Decoder.html#method.as_value_decoder) for why this
+    /// is useful: peek at a frame's shape, branch on a discriminator field, then
+    /// `serde_cbor::value::from_value` it into whichever concrete type is actually needed.
+    ///
+    /// The underlying `Value` decoder is built once and then reused across calls, so it keeps
+    /// whatever state it mutates between frames (eg. `SdMode::Once`).
+    pub fn decode_value(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<serde_cbor::Value>, CborError> {
+        if self.value_dec.is_none() {
+            self.value_dec = Some(self.dec.as_value_decoder());
+        }
+        self.value_dec.as_mut().unwrap().decode(src)
+    }
 }
 
-impl<'de, Dec: Deserialize<'de>, Enc: Serialize> Default for Codec<Dec, Enc> {
+impl<Dec: DeserializeOwned, Enc: Serialize> Default for Codec<Dec, Enc> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<'de, Dec: Deserialize<'de>, Enc: Serialize> IoDecoder for Codec<Dec, Enc> {
+impl<Dec: DeserializeOwned, Enc: Serialize> IoDecoder for Codec<Dec, Enc> {
     type Item = Dec;
     type Error = CborError;
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Dec>, CborError> {
@@ -257,7 +698,7 @@ impl<'de, Dec: Deserialize<'de>, Enc: Serialize> IoDecoder for Codec<Dec, Enc> {
     }
 }
 
-impl<'de, Dec: Deserialize<'de>, Enc: Serialize> IoEncoder for Codec<Dec, Enc> {
+impl<Dec: DeserializeOwned, Enc: Serialize> IoEncoder for Codec<Dec, Enc> {
     type Item = Enc;
     type Error = CborError;
     fn encode(&mut self, item: Enc, dst: &mut BytesMut) -> Result<(), CborError> {
@@ -376,4 +817,305 @@ mod tests {
         let encoder: Codec<(), _> = Codec::new().sd(SdMode::Once);
         encode(encoder);
     }
+
+    /// A frame whose header claims a multi-megabyte byte string must be rejected early when a
+    /// small `max_size` is configured, instead of waiting around for all those bytes to show up.
+    #[test]
+    fn decode_max_size_exceeded() {
+        let mut decoder: Decoder<serde_cbor::Value> = Decoder::new().max_size(16);
+        let mut buf = BytesMut::with_capacity(128);
+        // CBOR byte string (major type 2) header claiming a 5_000_000 byte payload.
+        buf.extend(&[0x5a, 0x00, 0x4c, 0x4b, 0x40]);
+        // Only a much smaller, partial chunk of the claimed payload has actually arrived.
+        buf.extend(&[0u8; 100]);
+        let len_before = buf.len();
+        decoder.decode(&mut buf).unwrap_err();
+        // The oversized frame must not be consumed, so callers can drop the connection.
+        assert_eq!(len_before, buf.len());
+    }
+
+    /// The same guard, exercised through the combined `Codec`.
+    #[test]
+    fn decode_max_size_exceeded_codec() {
+        let mut decoder: Codec<serde_cbor::Value, ()> = Codec::new().max_size(16);
+        let mut buf = BytesMut::with_capacity(128);
+        buf.extend(&[0x5a, 0x00, 0x4c, 0x4b, 0x40]);
+        buf.extend(&[0u8; 100]);
+        decoder.decode(&mut buf).unwrap_err();
+    }
+
+    /// The same guard, for the length-delimited path: a declared length far beyond `max_size`
+    /// must be rejected without waiting for the body to show up.
+    #[test]
+    fn decode_max_size_exceeded_length_delimited() {
+        let mut decoder: Decoder<TestData> = Decoder::new()
+            .max_size(16)
+            .frame_mode(FrameMode::LengthDelimited { header_bytes: 8 });
+        let mut buf = BytesMut::with_capacity(128);
+        buf.extend(&5_000_000u64.to_be_bytes());
+        buf.extend(&[0u8; 100]);
+        let len_before = buf.len();
+        decoder.decode(&mut buf).unwrap_err();
+        assert_eq!(len_before, buf.len());
+    }
+
+    /// The same guard, for the compressed path.
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn decode_max_size_exceeded_compressed() {
+        let mut decoder: Decoder<TestData> =
+            Decoder::new().max_size(16).compressed(Compression::Deflate);
+        let mut buf = BytesMut::with_capacity(128);
+        write_compressed_header(&mut buf, 5_000_000);
+        buf.extend(&[0u8; 100]);
+        let len_before = buf.len();
+        decoder.decode(&mut buf).unwrap_err();
+        assert_eq!(len_before, buf.len());
+    }
+
+    /// A hostile peer can declare a length-delimited body length up to `u64::MAX`; adding the
+    /// header's length to that must not overflow `usize` and panic (or silently wrap around to
+    /// something smaller than the header, which then panics on the slice below it).
+    #[test]
+    fn decode_length_delimited_declared_length_overflow() {
+        let mut decoder: Decoder<TestData> =
+            Decoder::new().frame_mode(FrameMode::LengthDelimited { header_bytes: 8 });
+        let mut buf = BytesMut::with_capacity(16);
+        buf.extend(&[0xff; 8]);
+        decoder.decode(&mut buf).unwrap_err();
+    }
+
+    /// Same overflow hazard, for the compressed path's declared payload length.
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn decode_compressed_declared_length_overflow() {
+        let mut decoder: Decoder<TestData> = Decoder::new().compressed(Compression::Deflate);
+        let mut buf = BytesMut::with_capacity(16);
+        buf.extend(&[0x5b]);
+        buf.extend(&[0xff; 8]);
+        decoder.decode(&mut buf).unwrap_err();
+    }
+
+    /// With `SdMode::Always`, every tagged frame decodes fine and the tag is stripped correctly.
+    #[test]
+    fn decode_expect_sd_always() {
+        let data = test_data();
+        let mut encoder = Encoder::new().sd(SdMode::Always);
+        let mut buf = BytesMut::with_capacity(128);
+        encoder.encode(data.clone(), &mut buf).unwrap();
+        encoder.encode(data.clone(), &mut buf).unwrap();
+        let mut decoder: Decoder<TestData> = Decoder::new().expect_sd(SdMode::Always);
+        let decoded = decoder.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(data, decoded);
+        let decoded = decoder.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(data, decoded);
+        assert!(buf.is_empty());
+    }
+
+    /// With `SdMode::Always`, a frame that isn't tagged is rejected, and left in the buffer.
+    #[test]
+    fn decode_expect_sd_missing() {
+        let data = test_data();
+        let mut encoder: Encoder<TestData> = Encoder::new();
+        let mut buf = BytesMut::with_capacity(128);
+        encoder.encode(data, &mut buf).unwrap();
+        let len_before = buf.len();
+        let mut decoder: Decoder<TestData> = Decoder::new().expect_sd(SdMode::Always);
+        decoder.decode(&mut buf).unwrap_err();
+        assert_eq!(len_before, buf.len());
+    }
+
+    /// With `SdMode::Once`, only the first frame needs the tag.
+    #[test]
+    fn decode_expect_sd_once() {
+        let data = test_data();
+        let mut encoder = Encoder::new().sd(SdMode::Once);
+        let mut buf = BytesMut::with_capacity(128);
+        encoder.encode(data.clone(), &mut buf).unwrap();
+        encoder.encode(data.clone(), &mut buf).unwrap();
+        let mut decoder: Decoder<TestData> = Decoder::new().expect_sd(SdMode::Once);
+        let decoded = decoder.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(data, decoded);
+        let decoded = decoder.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(data, decoded);
+        assert!(buf.is_empty());
+    }
+
+    /// If fewer than 3 bytes are buffered, we can't yet tell whether the tag is there, so decoding
+    /// must wait instead of misreading a partial tag as missing.
+    #[test]
+    fn decode_expect_sd_partial_tag() {
+        let mut decoder: Decoder<TestData> = Decoder::new().expect_sd(SdMode::Always);
+        let mut buf = BytesMut::with_capacity(4);
+        buf.extend(&SD_TAG[..2]);
+        assert!(decoder.decode(&mut buf).unwrap().is_none());
+        assert_eq!(2, buf.len());
+    }
+
+    /// A compressed frame round-trips, and a frame that hasn't fully arrived yet is left alone
+    /// (`Ok(None)`) instead of being decoded from truncated compressed data.
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn compressed_roundtrip() {
+        let data = test_data();
+        let mut encoder = Encoder::new().compressed(Compression::Deflate);
+        let mut encoded = BytesMut::with_capacity(128);
+        encoder.encode(data.clone(), &mut encoded).unwrap();
+
+        let mut decoder: Decoder<TestData> = Decoder::new().compressed(Compression::Deflate);
+        let mut partial = encoded.clone();
+        let missing_last_byte = partial.len() - 1;
+        partial.truncate(missing_last_byte);
+        assert!(decoder.decode(&mut partial).unwrap().is_none());
+        assert_eq!(missing_last_byte, partial.len());
+
+        let decoded = decoder.decode(&mut encoded).unwrap().unwrap();
+        assert_eq!(data, decoded);
+        assert!(encoded.is_empty());
+    }
+
+    /// The same round-trip, exercised through the combined `Codec`.
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn compressed_roundtrip_codec() {
+        let data = test_data();
+        let mut codec: Codec<TestData, TestData> = Codec::new().compressed(Compression::Deflate);
+        let mut buf = BytesMut::with_capacity(128);
+        codec.encode(data.clone(), &mut buf).unwrap();
+        let decoded = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(data, decoded);
+        assert!(buf.is_empty());
+    }
+
+    /// The self-describe tag lives inside the compressed payload, so `compressed` and
+    /// `expect_sd` must work together: the tag has to be found and stripped *after*
+    /// decompression, not on the still-compressed wire bytes.
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn compressed_roundtrip_with_expect_sd() {
+        let data = test_data();
+        let mut encoder = Encoder::new()
+            .compressed(Compression::Deflate)
+            .sd(SdMode::Always);
+        let mut buf = BytesMut::with_capacity(128);
+        encoder.encode(data.clone(), &mut buf).unwrap();
+
+        let mut decoder: Decoder<TestData> = Decoder::new()
+            .compressed(Compression::Deflate)
+            .expect_sd(SdMode::Always);
+        let decoded = decoder.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(data, decoded);
+        assert!(buf.is_empty());
+    }
+
+    /// A length-delimited frame round-trips, and a frame whose body hasn't fully arrived yet is
+    /// left alone (`Ok(None)`) instead of being decoded from a truncated body.
+    #[test]
+    fn length_delimited_roundtrip() {
+        let data = test_data();
+        let frame_mode = FrameMode::LengthDelimited { header_bytes: 4 };
+        let mut encoder = Encoder::new().frame_mode(frame_mode);
+        let mut encoded = BytesMut::with_capacity(128);
+        encoder.encode(data.clone(), &mut encoded).unwrap();
+
+        let mut decoder: Decoder<TestData> = Decoder::new().frame_mode(frame_mode);
+        let mut partial = encoded.clone();
+        let missing_last_byte = partial.len() - 1;
+        partial.truncate(missing_last_byte);
+        assert!(decoder.decode(&mut partial).unwrap().is_none());
+        assert_eq!(missing_last_byte, partial.len());
+
+        let decoded = decoder.decode(&mut encoded).unwrap().unwrap();
+        assert_eq!(data, decoded);
+        assert!(encoded.is_empty());
+    }
+
+    /// The same round-trip, exercised through the combined `Codec`.
+    #[test]
+    fn length_delimited_roundtrip_codec() {
+        let data = test_data();
+        let frame_mode = FrameMode::LengthDelimited { header_bytes: 2 };
+        let mut codec: Codec<TestData, TestData> = Codec::new().frame_mode(frame_mode);
+        let mut buf = BytesMut::with_capacity(128);
+        codec.encode(data.clone(), &mut buf).unwrap();
+        let decoded = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(data, decoded);
+        assert!(buf.is_empty());
+    }
+
+    /// A length-delimited frame whose declared length is longer than the CBOR item it actually
+    /// contains (eg. a peer padding frames) must be rejected rather than silently accepted.
+    #[test]
+    fn length_delimited_declared_length_mismatch() {
+        let data = test_data();
+        let mut encoder: Encoder<TestData> = Encoder::new();
+        let mut body = BytesMut::with_capacity(128);
+        encoder.encode(data, &mut body).unwrap();
+        body.extend(&[0u8; 3]);
+
+        let mut buf = BytesMut::with_capacity(128);
+        let len = body.len() as u64;
+        for i in (0..4).rev() {
+            buf.extend(&[(len >> (i * 8)) as u8]);
+        }
+        buf.extend(&body);
+
+        let mut decoder: Decoder<TestData> =
+            Decoder::new().frame_mode(FrameMode::LengthDelimited { header_bytes: 4 });
+        decoder.decode(&mut buf).unwrap_err();
+    }
+
+    /// `as_value_decoder` decodes the same bytes a concrete `Decoder<TestData>` would, just into
+    /// a `serde_cbor::Value`, and that `Value` converts back into the concrete type afterwards.
+    #[test]
+    fn as_value_decoder_roundtrip() {
+        let data = test_data();
+        let mut encoder: Encoder<TestData> = Encoder::new().sd(SdMode::Once);
+        let mut buf = BytesMut::with_capacity(128);
+        encoder.encode(data.clone(), &mut buf).unwrap();
+
+        let decoder: Decoder<TestData> = Decoder::new().max_size(64);
+        let mut value_decoder = decoder.as_value_decoder();
+        let value = value_decoder.decode(&mut buf).unwrap().unwrap();
+        assert!(buf.is_empty());
+        let decoded: TestData = serde_cbor::value::from_value(value).unwrap();
+        assert_eq!(data, decoded);
+    }
+
+    /// `Codec::decode_value` decodes (and consumes) a single frame as a `Value` without needing to
+    /// know the concrete `Dec` type up front.
+    #[test]
+    fn codec_decode_value() {
+        let data = test_data();
+        let mut codec: Codec<TestData, TestData> = Codec::new();
+        let mut buf = BytesMut::with_capacity(128);
+        codec.encode(data.clone(), &mut buf).unwrap();
+
+        let value = codec.decode_value(&mut buf).unwrap().unwrap();
+        assert!(buf.is_empty());
+        let decoded: TestData = serde_cbor::value::from_value(value).unwrap();
+        assert_eq!(data, decoded);
+    }
+
+    /// `Codec::decode_value` must reuse the same underlying `Value` decoder across calls, so that
+    /// with `SdMode::Once` the tag expectation correctly drops after the first frame instead of
+    /// being rebuilt (and re-armed) fresh every time.
+    #[test]
+    fn codec_decode_value_keeps_sd_state_across_calls() {
+        let data = test_data();
+        let mut encoder = Encoder::new().sd(SdMode::Once);
+        let mut buf = BytesMut::with_capacity(128);
+        encoder.encode(data.clone(), &mut buf).unwrap();
+        encoder.encode(data.clone(), &mut buf).unwrap();
+
+        let mut codec: Codec<TestData, TestData> = Codec::new().expect_sd(SdMode::Once);
+        let value = codec.decode_value(&mut buf).unwrap().unwrap();
+        let decoded: TestData = serde_cbor::value::from_value(value).unwrap();
+        assert_eq!(data, decoded);
+
+        let value = codec.decode_value(&mut buf).unwrap().unwrap();
+        let decoded: TestData = serde_cbor::value::from_value(value).unwrap();
+        assert_eq!(data, decoded);
+        assert!(buf.is_empty());
+    }
 }